@@ -0,0 +1,80 @@
+//! Error types for the lexer and parser.
+
+use thiserror::Error;
+
+use crate::lexer::{Span, TokenType};
+
+/// An error produced while lexing or parsing Jib source code.
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    /// A token was encountered that isn't one of the expected token types.
+    #[error("Expected {expected:?}, but got {found:?}")]
+    UnexpectedToken {
+        /// The token type that was actually found.
+        found: TokenType,
+        /// The token types that would have been accepted here.
+        expected: Vec<TokenType>,
+        /// The line number the token was found on, if known.
+        line: Option<usize>,
+        /// The 1-based column the token was found on, if known.
+        column: Option<usize>,
+        /// The byte-offset span of the token, if known.
+        span: Option<Span>,
+    },
+
+    /// A block (e.g. `<script>`, `<style>` or `<template>`) was never closed.
+    #[error("Missing closing `{tag}` tag.")]
+    UnclosedBlock {
+        /// The name of the tag that was left unclosed.
+        tag: &'static str,
+    },
+
+    /// The end of the file was reached before parsing could finish.
+    #[error("Unexpected end of file")]
+    UnexpectedEof,
+
+    /// A syntax error that doesn't fit any of the other variants.
+    #[error("{message}")]
+    Syntax {
+        /// A human-readable description of the problem.
+        message: String,
+        /// The line number the error applies to, if known.
+        line: Option<usize>,
+        /// The 1-based column the error applies to, if known.
+        column: Option<usize>,
+        /// The byte-offset span the error applies to, if known.
+        span: Option<Span>,
+    },
+}
+
+impl ParseError {
+    /// Returns the line number the error applies to, if known.
+    pub fn line_number(&self) -> Option<usize> {
+        match self {
+            ParseError::UnexpectedToken { line, .. } => *line,
+            ParseError::UnclosedBlock { .. } => None,
+            ParseError::UnexpectedEof => None,
+            ParseError::Syntax { line, .. } => *line,
+        }
+    }
+
+    /// Returns the 1-based column the error applies to, if known.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            ParseError::UnexpectedToken { column, .. } => *column,
+            ParseError::UnclosedBlock { .. } => None,
+            ParseError::UnexpectedEof => None,
+            ParseError::Syntax { column, .. } => *column,
+        }
+    }
+
+    /// Returns the byte-offset span the error applies to, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::UnexpectedToken { span, .. } => *span,
+            ParseError::UnclosedBlock { .. } => None,
+            ParseError::UnexpectedEof => None,
+            ParseError::Syntax { span, .. } => *span,
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Generator module for Jib files.
+//!
+//! Lowers an [ASTNode] into JavaScript source code.
+
+use sourcemap::{SourceMap, SourceMapBuilder};
+
+use crate::error::ParseError;
+use crate::parser::ASTNode;
+
+/// Escapes a string so it can be embedded in a double-quoted JavaScript string literal.
+fn escape_js_string(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Generates JavaScript for a single enum value, e.g. `"Bar": "Bar"`.
+fn generate_enum_value(node: &ASTNode) -> Result<String, ParseError> {
+    match node {
+        ASTNode::EnumValue(name) => Ok(format!(
+            "\"{}\": \"{}\"",
+            escape_js_string(name),
+            escape_js_string(name)
+        )),
+        _ => Err(ParseError::Syntax {
+            message: format!("Expected an enum value, but got {:?}", node),
+            line: None,
+            column: None,
+            span: None,
+        }),
+    }
+}
+
+/// Generates JavaScript for an enum declaration, e.g.
+/// `const Foo = Object.freeze({ "Bar": "Bar", "Baz": "Baz" });`.
+fn generate_enum(name: &str, values: &[ASTNode]) -> Result<String, ParseError> {
+    let values = values
+        .iter()
+        .map(generate_enum_value)
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    Ok(format!("const {} = Object.freeze({{ {} }});", name, values))
+}
+
+/// Generates JavaScript for a single statement within a `<script>` block.
+fn generate_statement(node: &ASTNode) -> Result<String, ParseError> {
+    match node {
+        ASTNode::Enum(name, values) => generate_enum(name, values),
+        ASTNode::Comment(text) => Ok(format!("// {}", text)),
+        _ => Err(ParseError::Syntax {
+            message: format!("Cannot generate code for {:?}", node),
+            line: None,
+            column: None,
+            span: None,
+        }),
+    }
+}
+
+/// Generates JavaScript for a single top-level block.
+fn generate_block(node: &ASTNode) -> Result<String, ParseError> {
+    match node {
+        ASTNode::Template(html) => Ok(format!(
+            "document.body.innerHTML += \"{}\";",
+            escape_js_string(html)
+        )),
+        ASTNode::Style(css) => Ok(format!(
+            "const style = document.createElement(\"style\");\nstyle.textContent = \"{}\";\ndocument.head.appendChild(style);",
+            escape_js_string(css)
+        )),
+        ASTNode::Script(statements) => statements
+            .iter()
+            .map(generate_statement)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|lines| lines.join("\n")),
+        _ => Err(ParseError::Syntax {
+            message: format!("Unexpected node at the root: {:?}", node),
+            line: None,
+            column: None,
+            span: None,
+        }),
+    }
+}
+
+/// Generates JavaScript source code from an [ASTNode].
+///
+/// # Examples
+///
+/// ```
+/// use jib::generator::generate;
+/// use jib::parser::parse;
+/// use jib::lexer::Lexer;
+///
+/// let mut lexer = Lexer::new().load_source("<script>enum Foo = { Bar|Baz }</script>".to_string());
+/// let ast_root = parse(&mut lexer).unwrap();
+/// assert_eq!(
+///     generate(&ast_root).unwrap(),
+///     "const Foo = Object.freeze({ \"Bar\": \"Bar\", \"Baz\": \"Baz\" });"
+/// );
+/// ```
+pub fn generate(ast: &ASTNode) -> Result<String, ParseError> {
+    match ast {
+        ASTNode::Root(blocks) => blocks
+            .iter()
+            .map(generate_block)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|lines| lines.join("\n")),
+        _ => Err(ParseError::Syntax {
+            message: format!("Expected the root of the AST, but got {:?}", ast),
+            line: None,
+            column: None,
+            span: None,
+        }),
+    }
+}
+
+/// Generates JavaScript source code from an [ASTNode], along with a Source Map v3 mapping each
+/// emitted top-level block back to the position it was generated from.
+///
+/// `source_positions` must hold one 1-based `(line, column)` pair per child of `ast`'s
+/// [ASTNode::Root], in order — typically obtained by resolving [parse_with_spans](crate::parser::parse_with_spans)'s
+/// spans with [Lexer::position_at](crate::lexer::Lexer::position_at).
+///
+/// # Examples
+///
+/// ```
+/// use jib::generator::generate_with_sourcemap;
+/// use jib::parser::parse_with_spans;
+/// use jib::lexer::Lexer;
+///
+/// let mut lexer = Lexer::new().load_source("<template>Hi!</template>".to_string());
+/// let (ast_root, spans) = parse_with_spans(&mut lexer).unwrap();
+/// let source_positions: Vec<(usize, usize)> = spans
+///     .iter()
+///     .map(|span| lexer.position_at(span.start))
+///     .collect();
+/// let (js, _source_map) =
+///     generate_with_sourcemap(&ast_root, &source_positions, "index.jib").unwrap();
+/// assert_eq!(js, "document.body.innerHTML += \"Hi!\";\n");
+/// ```
+pub fn generate_with_sourcemap(
+    ast: &ASTNode,
+    source_positions: &[(usize, usize)],
+    source_name: &str,
+) -> Result<(String, SourceMap), ParseError> {
+    let blocks = match ast {
+        ASTNode::Root(blocks) => blocks,
+        _ => {
+            return Err(ParseError::Syntax {
+                message: format!("Expected the root of the AST, but got {:?}", ast),
+                line: None,
+                column: None,
+                span: None,
+            });
+        }
+    };
+
+    if blocks.len() != source_positions.len() {
+        return Err(ParseError::Syntax {
+            message: "Number of blocks does not match number of source positions".to_string(),
+            line: None,
+            column: None,
+            span: None,
+        });
+    }
+
+    let mut builder = SourceMapBuilder::new(None);
+    let source_id = builder.add_source(source_name);
+    let mut output = String::new();
+    let mut dst_line: u32 = 0;
+
+    for (block, &(src_line, src_column)) in blocks.iter().zip(source_positions) {
+        let code = generate_block(block)?;
+        builder.add_raw(
+            dst_line,
+            0,
+            (src_line - 1) as u32,
+            (src_column - 1) as u32,
+            Some(source_id),
+            None,
+            false,
+        );
+        dst_line += 1 + code.matches('\n').count() as u32;
+        output.push_str(&code);
+        output.push('\n');
+    }
+
+    Ok((output, builder.into_sourcemap()))
+}
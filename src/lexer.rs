@@ -5,6 +5,8 @@ use regex::Regex;
 use std::fs;
 use std::path::Path;
 
+use crate::error::ParseError;
+
 /// The [Token] type.
 #[allow(missing_docs)]
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
@@ -46,13 +48,29 @@ pub struct Token {
     /// The file path of the source code.
     pub filepath: Option<String>,
 
-    /// The line number in the source code.
+    /// The 1-based line number [Token::span]'s start falls on.
     pub line_number: usize,
 
+    /// The 1-based column in the source code, derived from [Token::span]'s start and the offset
+    /// of the last newline before it.
+    pub column: usize,
+
+    /// The byte-offset range of this token in the source code.
+    pub span: Span,
+
     /// The original string value within the source code.
     pub value: String,
 }
 
+/// A byte-offset range into the source code, used for diagnostics and source maps.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub struct Span {
+    /// The byte offset this span starts at.
+    pub start: usize,
+    /// The byte offset this span ends at (exclusive).
+    pub end: usize,
+}
+
 /// A [Lexer] state in which the source code has not been loaded yet.
 pub struct MissingSource {}
 
@@ -65,6 +83,146 @@ pub trait LexerState {}
 impl LexerState for MissingSource {}
 impl LexerState for LoadedSource {}
 
+/// A lexing mode, each owning its own set of token rules.
+///
+/// States are kept on a stack so the [Lexer] can switch rule sets when entering or leaving a
+/// `<script>`, `<style>` or `<template>` block, instead of applying one flat rule list
+/// everywhere. This is what lets `<style>` and `<template>` content be tokenized as raw text
+/// rather than being misread using the `<script>` keyword/operator rules.
+///
+/// Each state's rule set is its own independent flat list, not a child rule set layered over a
+/// parent's; rules shared across states (currently just whitespace/newline handling, see
+/// [compile_common_rules]) are duplicated at compile time into the lists of the states that use
+/// them ([State::TopLevel] and [State::InScript]) rather than composed through inheritance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Outside of any block. Only tag-opening rules and whitespace are recognized here.
+    TopLevel,
+    /// Inside a `<script>` block. The full keyword/operator rule set applies.
+    InScript,
+    /// Inside a `<style>` block. Only the nesting `<style>` tags and raw CSS text are recognized.
+    InStyle,
+    /// Inside a `<template>` block. Only the nesting `<template>` tags and raw HTML text are
+    /// recognized.
+    InTemplate,
+}
+
+/// The compiled rule sets for every [State].
+#[derive(Debug, Clone)]
+struct RuleSets {
+    top_level: Vec<(TokenType, Regex)>,
+    in_script: Vec<(TokenType, Regex)>,
+    in_style: Vec<(TokenType, Regex)>,
+    in_template: Vec<(TokenType, Regex)>,
+}
+
+impl RuleSets {
+    /// Returns the flat rule list compiled for `state`. States do not inherit rules from one
+    /// another; each list is self-contained, including its own copy of any shared rules.
+    fn for_state(&self, state: State) -> &[(TokenType, Regex)] {
+        match state {
+            State::TopLevel => &self.top_level,
+            State::InScript => &self.in_script,
+            State::InStyle => &self.in_style,
+            State::InTemplate => &self.in_template,
+        }
+    }
+}
+
+/// Rules shared by every state that recognizes regular source text: a single newline character,
+/// or a run of other whitespace.
+///
+/// The [TokenType::Newline] rule must be tried before [TokenType::Whitespace], since `\s` also
+/// matches newlines and would otherwise swallow them into a single token, losing line tracking.
+fn compile_common_rules() -> Vec<(TokenType, Regex)> {
+    vec![
+        (TokenType::Newline, Regex::new(r"^([\n\r])").unwrap()),
+        (TokenType::Whitespace, Regex::new(r"^([\s\t]+)").unwrap()),
+    ]
+}
+
+/// Rules for [State::TopLevel]: only the tags that open a block, plus whitespace.
+fn compile_top_level_rules() -> Vec<(TokenType, Regex)> {
+    let mut rules = vec![
+        (
+            TokenType::TagScriptStart,
+            Regex::new(r"^(<\s*script\s*>)").unwrap(),
+        ),
+        (
+            TokenType::TagStyleStart,
+            Regex::new(r"^(<\s*style\s*>)").unwrap(),
+        ),
+        (
+            TokenType::TagTemplateStart,
+            Regex::new(r"^(<\s*template\s*>)").unwrap(),
+        ),
+    ];
+    rules.extend(compile_common_rules());
+    rules
+}
+
+/// Rules for [State::InScript]: the full keyword/operator rule set, plus the closing tag.
+fn compile_script_rules() -> Vec<(TokenType, Regex)> {
+    let mut rules = vec![
+        (TokenType::Comment, Regex::new(r"^#\s*([^\n\r]*)").unwrap()),
+        (TokenType::Keyword, Regex::new(r"^(enum)\s").unwrap()),
+        (TokenType::Keyword, Regex::new(r"^(type)\s").unwrap()),
+        (TokenType::Keyword, Regex::new(r"^(fn)\s").unwrap()),
+        (
+            TokenType::TagScriptEnd,
+            Regex::new(r"^(<\/\s*script\s*>)").unwrap(),
+        ),
+        (TokenType::StringLiteral, Regex::new("^\"(.*?)\"").unwrap()),
+        (TokenType::Equal, Regex::new(r"^(=)").unwrap()),
+        (TokenType::Minus, Regex::new(r"^(-)").unwrap()),
+        (TokenType::Plus, Regex::new(r"^(\+)").unwrap()),
+        (TokenType::CurlyBracketOpen, Regex::new(r"^(\{)").unwrap()),
+        (TokenType::CurlyBracketClose, Regex::new(r"^(\})").unwrap()),
+        (TokenType::BracketOpen, Regex::new(r"^(\()").unwrap()),
+        (TokenType::BracketClose, Regex::new(r"^(\))").unwrap()),
+        (TokenType::SemiColon, Regex::new(r"^(;)").unwrap()),
+        (TokenType::Pipe, Regex::new(r"^(\|)").unwrap()),
+        (TokenType::Comma, Regex::new(r"^(,)").unwrap()),
+        (TokenType::Period, Regex::new(r"^(\.)").unwrap()),
+        (TokenType::Text, Regex::new(r"^([\w:][\w\-:]*)").unwrap()),
+    ];
+    rules.extend(compile_common_rules());
+    rules
+}
+
+/// Rules for [State::InStyle]: the nesting tags, plus a catch-all for raw CSS text.
+///
+/// The regex crate doesn't support lookaround, so raw text is matched up to (but not including)
+/// the next `<`, which is enough since every tag of interest starts with one.
+fn compile_style_rules() -> Vec<(TokenType, Regex)> {
+    vec![
+        (
+            TokenType::TagStyleStart,
+            Regex::new(r"^(<\s*style\s*>)").unwrap(),
+        ),
+        (
+            TokenType::TagStyleEnd,
+            Regex::new(r"^(<\/\s*style\s*>)").unwrap(),
+        ),
+        (TokenType::Text, Regex::new(r"^([^<]+)").unwrap()),
+    ]
+}
+
+/// Rules for [State::InTemplate]: the nesting tags, plus a catch-all for raw HTML text.
+fn compile_template_rules() -> Vec<(TokenType, Regex)> {
+    vec![
+        (
+            TokenType::TagTemplateStart,
+            Regex::new(r"^(<\s*template\s*>)").unwrap(),
+        ),
+        (
+            TokenType::TagTemplateEnd,
+            Regex::new(r"^(<\/\s*template\s*>)").unwrap(),
+        ),
+        (TokenType::Text, Regex::new(r"^([^<]+)").unwrap()),
+    ]
+}
+
 /// Turns source code into a stream of [Token]s.
 ///
 /// # Example
@@ -80,7 +238,7 @@ impl LexerState for LoadedSource {}
 /// };
 /// #
 /// # let mut lexer = Lexer::new().load_source("\n\n".to_string());
-/// # assert_eq!(lexer.last().unwrap().line_number, 3);
+/// # assert_eq!(lexer.last().unwrap().line_number, 2);
 ///
 /// # let mut lexer = Lexer::new().load_source("<script>Hello</script>".to_string());
 /// # assert_eq!(lexer.count(), 3);
@@ -107,7 +265,9 @@ pub struct Lexer<S: LexerState> {
     filepath: Option<String>,
     offset: usize,
     line_number: usize,
-    regexes: Option<Vec<(TokenType, Regex)>>,
+    line_start_offset: usize,
+    regexes: Option<RuleSets>,
+    states: Vec<State>,
     peeked_token: Option<Token>,
     marker: std::marker::PhantomData<S>,
 }
@@ -119,58 +279,15 @@ impl Lexer<MissingSource> {
     /// new Lexer that [Lexer::load_file()] returns.
     pub fn new() -> Self {
         Self {
-            regexes: Some(Self::compile_regexes()),
+            regexes: Some(RuleSets {
+                top_level: compile_top_level_rules(),
+                in_script: compile_script_rules(),
+                in_style: compile_style_rules(),
+                in_template: compile_template_rules(),
+            }),
             ..Default::default()
         }
     }
-
-    fn compile_regexes() -> Vec<(TokenType, Regex)> {
-        vec![
-            (TokenType::Comment, Regex::new(r"^#\s*([^\n\r]*)").unwrap()),
-            (TokenType::Keyword, Regex::new(r"^(enum)\s").unwrap()),
-            (TokenType::Keyword, Regex::new(r"^(type)\s").unwrap()),
-            (TokenType::Keyword, Regex::new(r"^(fn)\s").unwrap()),
-            (
-                TokenType::TagScriptStart,
-                Regex::new(r"^(<\s*script\s*>)").unwrap(),
-            ),
-            (
-                TokenType::TagScriptEnd,
-                Regex::new(r"^(<\/\s*script\s*>)").unwrap(),
-            ),
-            (
-                TokenType::TagStyleStart,
-                Regex::new(r"^(<\s*style\s*>)").unwrap(),
-            ),
-            (
-                TokenType::TagStyleEnd,
-                Regex::new(r"^(<\/\s*style\s*>)").unwrap(),
-            ),
-            (
-                TokenType::TagTemplateStart,
-                Regex::new(r"^(<\s*template\s*>)").unwrap(),
-            ),
-            (
-                TokenType::TagTemplateEnd,
-                Regex::new(r"^(<\/\s*template\s*>)").unwrap(),
-            ),
-            (TokenType::Newline, Regex::new(r"^([\n\r])").unwrap()),
-            (TokenType::Whitespace, Regex::new(r"^([\s\t]+)").unwrap()),
-            (TokenType::StringLiteral, Regex::new("^\"(.*?)\"").unwrap()),
-            (TokenType::Equal, Regex::new(r"^(=)").unwrap()),
-            (TokenType::Minus, Regex::new(r"^(-)").unwrap()),
-            (TokenType::Plus, Regex::new(r"^(\+)").unwrap()),
-            (TokenType::CurlyBracketOpen, Regex::new(r"^(\{)").unwrap()),
-            (TokenType::CurlyBracketClose, Regex::new(r"^(\})").unwrap()),
-            (TokenType::BracketOpen, Regex::new(r"^(\()").unwrap()),
-            (TokenType::BracketClose, Regex::new(r"^(\))").unwrap()),
-            (TokenType::SemiColon, Regex::new(r"^(;)").unwrap()),
-            (TokenType::Pipe, Regex::new(r"^(\|)").unwrap()),
-            (TokenType::Comma, Regex::new(r"^(,)").unwrap()),
-            (TokenType::Period, Regex::new(r"^(\.)").unwrap()),
-            (TokenType::Text, Regex::new(r"^([\w:][\w\-:]*)").unwrap()),
-        ]
-    }
 }
 
 impl<S> Lexer<S>
@@ -212,7 +329,9 @@ where
             filepath: None,
             offset: 0,
             line_number: 1,
+            line_start_offset: 0,
             regexes: None,
+            states: vec![State::TopLevel],
             peeked_token: None,
             marker: std::marker::PhantomData,
         }
@@ -220,12 +339,21 @@ where
 }
 
 impl Lexer<LoadedSource> {
-    fn create_token(&self, token_type: TokenType, value: Option<String>) -> Token {
+    fn create_token(
+        &self,
+        token_type: TokenType,
+        value: Option<String>,
+        span: Span,
+        line_number: usize,
+        column: usize,
+    ) -> Token {
         let token = Token {
             token_type,
             value: value.unwrap_or_default(),
             filepath: self.filepath.clone(),
-            line_number: self.line_number,
+            line_number,
+            column,
+            span,
         };
         debug!("{:?}", token);
         token
@@ -242,25 +370,55 @@ impl Lexer<LoadedSource> {
     }
 
     /// Returns an error when an unexpected token is encountered.
-    pub fn expect_token(
-        &mut self,
-        expected_token_type: TokenType,
-    ) -> Result<Token, (Option<usize>, String)> {
-        if let Some(token) = self.next() {
-            if token.token_type == expected_token_type {
-                Ok(token)
-            } else {
-                Err((
-                    Some(token.line_number),
-                    format!(
-                        "Expected {:?}, but got {:?}",
-                        expected_token_type, token.token_type
-                    ),
-                ))
+    pub fn expect_token(&mut self, expected_token_type: TokenType) -> Result<Token, ParseError> {
+        match self.next() {
+            Some(token) if token.token_type == expected_token_type => Ok(token),
+            Some(token) => Err(ParseError::UnexpectedToken {
+                found: token.token_type,
+                expected: vec![expected_token_type],
+                line: Some(token.line_number),
+                column: Some(token.column),
+                span: Some(token.span),
+            }),
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    /// Returns the current byte offset into the source, i.e. the position immediately after the
+    /// most recently produced token.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the current lexing-state nesting depth.
+    ///
+    /// Pushed by one on every `TagScriptStart`/`TagStyleStart`/`TagTemplateStart` and popped by
+    /// one on the matching end tag (see [Iterator::next]'s state transitions), so the parser can
+    /// tell when a `<style>`/`<template>` block — and any same-tag nesting inside it — has fully
+    /// closed, without re-counting tag tokens itself.
+    pub(crate) fn state_depth(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Computes the 1-based `(line, column)` a byte offset corresponds to.
+    ///
+    /// Used to translate a [Span] into source map coordinates.
+    pub fn position_at(&self, offset: usize) -> (usize, usize) {
+        let source = self
+            .source
+            .as_ref()
+            .expect("should have loaded source code");
+        let mut line = 1;
+        let mut line_start = 0;
+
+        for (i, c) in source[..offset].char_indices() {
+            if c == '\n' || c == '\r' {
+                line += 1;
+                line_start = i + c.len_utf8();
             }
-        } else {
-            Err((None, "Unexpected end of file".to_string()))
         }
+
+        (line, offset - line_start + 1)
     }
 }
 
@@ -317,10 +475,16 @@ impl Iterator for Lexer<LoadedSource> {
             return None;
         }
 
+        let current_state = *self
+            .states
+            .last()
+            .expect("state stack should never be empty");
+
         let (token_type, value, length) = self
             .regexes
             .as_ref()
             .expect("should have compiled regexes")
+            .for_state(current_state)
             .iter()
             // Generate regex matches
             .map(|(t, r)| (t, r.captures(left_to_parse)))
@@ -340,12 +504,44 @@ impl Iterator for Lexer<LoadedSource> {
             // with them.
             .unwrap_or((&TokenType::Unknown, left_to_parse[0..1].to_string(), 1));
 
-        self.offset += length;
+        let start = self.offset;
+        let end = start + length;
+        let column = start - self.line_start_offset + 1;
+        // Snapshot the line number at the token's start before scanning its own text for
+        // embedded newlines below, which mutates `self.line_number` for the *next* token.
+        let line_number = self.line_number;
 
-        if *token_type == TokenType::Newline {
-            self.line_number += 1;
+        self.offset = end;
+
+        let mut last_newline_end = None;
+        for (i, c) in value.char_indices() {
+            if c == '\n' || c == '\r' {
+                self.line_number += 1;
+                last_newline_end = Some(start + i + c.len_utf8());
+            }
+        }
+        if let Some(offset) = last_newline_end {
+            self.line_start_offset = offset;
+        }
+
+        match token_type {
+            TokenType::TagScriptStart => self.states.push(State::InScript),
+            TokenType::TagStyleStart => self.states.push(State::InStyle),
+            TokenType::TagTemplateStart => self.states.push(State::InTemplate),
+            TokenType::TagScriptEnd | TokenType::TagStyleEnd | TokenType::TagTemplateEnd
+                if self.states.len() > 1 =>
+            {
+                self.states.pop();
+            }
+            _ => {}
         }
 
-        Some(self.create_token(token_type.clone(), Some(value)))
+        Some(self.create_token(
+            token_type.clone(),
+            Some(value),
+            Span { start, end },
+            line_number,
+            column,
+        ))
     }
 }
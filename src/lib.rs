@@ -2,7 +2,7 @@
 //!
 //! A Jib to Javascript compiler.
 //!
-//! This library will contain a [lexer], [parser] and generator to compile from Jib to Javascript.
+//! This library contains a [lexer], [parser] and [generator] to compile from Jib to Javascript.
 //! For more information about Jib, see [Jib's README file on Github][jib].
 //!
 //! [jib]: https://github.com/nootr/jib
@@ -12,5 +12,7 @@
 )]
 #![warn(missing_docs)]
 
+pub mod error;
+pub mod generator;
 pub mod lexer;
 pub mod parser;
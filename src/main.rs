@@ -1,8 +1,15 @@
 use clap::Parser as ArgParser;
 use log::debug;
+use std::fs;
+use std::path::Path;
 use walkdir::WalkDir;
 
-use jib::{lexer::Lexer, parser::parse};
+use jib::error::ParseError;
+use jib::{
+    generator::{generate, generate_with_sourcemap},
+    lexer::Lexer,
+    parser::{parse, parse_with_spans},
+};
 
 /// A Jib to Javascript compiler.
 ///
@@ -13,6 +20,28 @@ pub struct Args {
     /// The source directory.
     #[arg(index = 1, default_value_t = String::from("./"))]
     pub directory: String,
+
+    /// Emit a Source Map v3 file alongside each compiled file, mapping it back to its Jib source.
+    #[arg(long)]
+    pub sourcemaps: bool,
+}
+
+/// Renders a [ParseError] as `[file:line:col] message`, falling back to `[file:line] message` or
+/// `[file] message` as fewer positions are known.
+fn format_error(filepath: &Path, error: &ParseError) -> String {
+    match (error.line_number(), error.column()) {
+        (Some(line_number), Some(column)) => format!(
+            "[{}:{}:{}] {}",
+            filepath.display(),
+            line_number,
+            column,
+            error
+        ),
+        (Some(line_number), None) => {
+            format!("[{}:{}] {}", filepath.display(), line_number, error)
+        }
+        (None, _) => format!("[{}] {}", filepath.display(), error),
+    }
 }
 
 fn main() -> Result<(), String> {
@@ -29,11 +58,48 @@ fn main() -> Result<(), String> {
         let filepath = entry.path();
         debug!("Opening file: `{}`", filepath.display());
         let mut lexer = lexer.load_file(filepath);
-        let ast_root = parse(&mut lexer).map_err(|(line_number, message)| match line_number {
-            Some(line_number) => format!("[{}:{}] {}", filepath.display(), line_number, message),
-            None => format!("[{}] {}", filepath.display(), message),
-        })?;
-        debug!("{:?}", ast_root);
+        let output_path = filepath.with_extension("js");
+
+        if args.sourcemaps {
+            let (ast_root, spans) =
+                parse_with_spans(&mut lexer).map_err(|error| format_error(filepath, &error))?;
+            debug!("{:?}", ast_root);
+
+            let source_positions: Vec<(usize, usize)> = spans
+                .iter()
+                .map(|span| lexer.position_at(span.start))
+                .collect();
+            let (mut js, source_map) = generate_with_sourcemap(
+                &ast_root,
+                &source_positions,
+                &filepath.display().to_string(),
+            )
+            .map_err(|error| format_error(filepath, &error))?;
+
+            let map_path = output_path.with_extension("js.map");
+            let map_filename = map_path
+                .file_name()
+                .expect("should have a file name")
+                .to_string_lossy();
+            js.push_str(&format!("\n//# sourceMappingURL={}\n", map_filename));
+            fs::write(&output_path, js)
+                .map_err(|error| format!("[{}] {}", output_path.display(), error))?;
+
+            let mut map_bytes = Vec::new();
+            source_map
+                .to_writer(&mut map_bytes)
+                .map_err(|error| format!("[{}] {}", map_path.display(), error))?;
+            fs::write(&map_path, map_bytes)
+                .map_err(|error| format!("[{}] {}", map_path.display(), error))?;
+        } else {
+            let ast_root =
+                parse(&mut lexer).map_err(|error| format_error(filepath, &error))?;
+            debug!("{:?}", ast_root);
+
+            let js = generate(&ast_root).map_err(|error| format_error(filepath, &error))?;
+            fs::write(&output_path, js)
+                .map_err(|error| format!("[{}] {}", output_path.display(), error))?;
+        }
     }
     Ok(())
 }
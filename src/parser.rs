@@ -1,6 +1,7 @@
 //! Parser module for Jib files.
 
-use crate::lexer::{Lexer, LoadedSource, Peekable, TokenType};
+use crate::error::ParseError;
+use crate::lexer::{Lexer, LoadedSource, Peekable, Span, TokenType};
 
 /// A node in an Abstract Syntax Tree.
 #[derive(Debug, PartialEq, Eq)]
@@ -38,26 +39,20 @@ pub enum ASTNode {
 ///     ])
 /// );
 /// ```
-fn parse_template_block(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
-    let mut open_blocks = 1;
+fn parse_template_block(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
+    let depth = tokens.state_depth();
     let mut value = String::new();
 
-    while open_blocks > 0 {
+    loop {
         let token = tokens
             .next()
-            .ok_or_else(|| (None, "Missing closing </template> tag.".to_string()))?;
+            .ok_or(ParseError::UnclosedBlock { tag: "template" })?;
 
-        match token.token_type {
-            TokenType::TagTemplateStart => open_blocks += 1,
-            TokenType::TagTemplateEnd => open_blocks -= 1,
-            _ => {}
+        if tokens.state_depth() < depth {
+            break;
         }
 
-        if open_blocks > 0 {
-            value.push_str(&token.value);
-        }
+        value.push_str(&token.value);
     }
 
     Ok(Some(ASTNode::Template(value)))
@@ -80,31 +75,51 @@ fn parse_template_block(
 ///     ])
 /// );
 /// ```
-fn parse_style_block(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
-    let mut open_blocks = 1;
+fn parse_style_block(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
+    let depth = tokens.state_depth();
     let mut value = String::new();
 
-    while open_blocks > 0 {
+    loop {
         let token = tokens
             .next()
-            .ok_or_else(|| (None, "Missing closing </style> tag.".to_string()))?;
+            .ok_or(ParseError::UnclosedBlock { tag: "style" })?;
 
-        match token.token_type {
-            TokenType::TagStyleStart => open_blocks += 1,
-            TokenType::TagStyleEnd => open_blocks -= 1,
-            _ => {}
+        if tokens.state_depth() < depth {
+            break;
         }
 
-        if open_blocks > 0 {
-            value.push_str(&token.value);
-        }
+        value.push_str(&token.value);
     }
 
     Ok(Some(ASTNode::Style(value)))
 }
 
+/// Returns `true` if `name` is a valid JavaScript identifier, i.e. suitable for emission as a
+/// raw, unquoted identifier in generated code. Only ASCII identifiers are accepted; non-ASCII
+/// names are rejected even where JavaScript would allow them.
+///
+/// The `Text` token itself is lexed more liberally (`^([\w:][\w\-:]*)`, see
+/// `compile_script_rules`), so an enum name like `1-Foo` or `a:b` tokenizes fine but would
+/// generate invalid JavaScript if emitted as-is.
+fn is_valid_js_identifier(name: &str) -> bool {
+    const RESERVED_WORDS: &[&str] = &[
+        "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default",
+        "delete", "do", "else", "enum", "export", "extends", "false", "finally", "for", "function",
+        "if", "implements", "import", "in", "instanceof", "interface", "let", "new", "null",
+        "package", "private", "protected", "public", "return", "static", "super", "switch", "this",
+        "throw", "true", "try", "typeof", "var", "void", "while", "with", "yield",
+    ];
+    if RESERVED_WORDS.contains(&name) {
+        return false;
+    }
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c == '$' || c.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c == '_' || c == '$' || c.is_ascii_alphanumeric())
+}
+
 /// Parses an enum declaration.
 ///
 /// # Examples
@@ -130,12 +145,21 @@ fn parse_style_block(
 ///     ])
 /// );
 /// ```
-fn parse_enum(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
+fn parse_enum(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
     tokens.expect_token(TokenType::Keyword)?;
     tokens.flush_whitespace();
     let name_token = tokens.expect_token(TokenType::Text)?;
+    if !is_valid_js_identifier(&name_token.value) {
+        return Err(ParseError::Syntax {
+            message: format!(
+                "`{}` is not a valid enum name; names must be valid JavaScript identifiers",
+                name_token.value
+            ),
+            line: Some(name_token.line_number),
+            column: Some(name_token.column),
+            span: Some(name_token.span),
+        });
+    }
     tokens.flush_whitespace();
     tokens.expect_token(TokenType::Equal)?;
     tokens.flush_whitespace();
@@ -147,12 +171,7 @@ fn parse_enum(
         let enum_value_token = tokens.expect_token(TokenType::Text)?;
         enum_values.push(ASTNode::EnumValue(enum_value_token.value));
         tokens.flush_whitespace();
-        let delimiter_token = tokens.next().ok_or_else(|| {
-            (
-                Some(enum_value_token.line_number),
-                "Expected `}` or `|`".to_string(),
-            )
-        })?;
+        let delimiter_token = tokens.next().ok_or(ParseError::UnexpectedEof)?;
         match delimiter_token.token_type {
             TokenType::CurlyBracketClose => {
                 break;
@@ -161,7 +180,13 @@ fn parse_enum(
                 continue;
             }
             _ => {
-                return Err((Some(name_token.line_number), "Syntax error".to_string()));
+                return Err(ParseError::UnexpectedToken {
+                    found: delimiter_token.token_type,
+                    expected: vec![TokenType::CurlyBracketClose, TokenType::Pipe],
+                    line: Some(delimiter_token.line_number),
+                    column: Some(delimiter_token.column),
+                    span: Some(delimiter_token.span),
+                });
             }
         }
     }
@@ -187,17 +212,13 @@ fn parse_enum(
 ///     ])
 /// );
 /// ```
-fn parse_comment(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
+fn parse_comment(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
     let token = tokens.expect_token(TokenType::Comment)?;
 
     Ok(Some(ASTNode::Comment(token.value)))
 }
 
-fn parse_statement(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
+fn parse_statement(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
     let token = tokens.peek().expect("should have token");
 
     match token.token_type {
@@ -210,7 +231,7 @@ fn parse_statement(
             tokens.next();
             Ok(None)
             // TODO: replace with following Err
-            //Err((Some(token.line_number), "Syntax error".to_string()))
+            //Err(ParseError::Syntax { message: "Syntax error".to_string(), line: Some(token.line_number), column: Some(token.column), span: Some(token.span) })
         }
     }
 }
@@ -232,14 +253,12 @@ fn parse_statement(
 ///     ])
 /// );
 /// ```
-fn parse_script_block(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
+fn parse_script_block(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
     let mut statements = Vec::new();
     loop {
         let token = tokens
             .peek()
-            .ok_or_else(|| (None, "Missing closing </script> tag.".to_string()))?;
+            .ok_or(ParseError::UnclosedBlock { tag: "script" })?;
 
         match token.token_type {
             TokenType::TagScriptEnd => {
@@ -259,28 +278,31 @@ fn parse_script_block(
     Ok(Some(ASTNode::Script(statements)))
 }
 
-fn parse_html_block(
-    tokens: &mut Lexer<LoadedSource>,
-) -> Result<Option<ASTNode>, (Option<usize>, String)> {
+fn parse_html_block(tokens: &mut Lexer<LoadedSource>) -> Result<Option<ASTNode>, ParseError> {
     tokens.flush_whitespace();
 
-    let next_token = tokens
-        .next()
-        .ok_or_else(|| (None, "Unexpected end of file".to_string()))?;
+    let next_token = tokens.next().ok_or(ParseError::UnexpectedEof)?;
 
     match next_token.token_type {
         TokenType::TagTemplateStart => parse_template_block(tokens),
         TokenType::TagStyleStart => parse_style_block(tokens),
         TokenType::TagScriptStart => parse_script_block(tokens),
-        _ => Err((
-            Some(next_token.line_number),
-            "Expected a <template>, <style> or <script> block".to_string(),
-        )),
+        _ => Err(ParseError::UnexpectedToken {
+            found: next_token.token_type,
+            expected: vec![
+                TokenType::TagTemplateStart,
+                TokenType::TagStyleStart,
+                TokenType::TagScriptStart,
+            ],
+            line: Some(next_token.line_number),
+            column: Some(next_token.column),
+            span: Some(next_token.span),
+        }),
     }
 }
 
 /// Takes tokens and restructures them into an Abstract Syntax Tree.
-pub fn parse(tokens: &mut Lexer<LoadedSource>) -> Result<ASTNode, (Option<usize>, String)> {
+pub fn parse(tokens: &mut Lexer<LoadedSource>) -> Result<ASTNode, ParseError> {
     let mut html_blocks = Vec::new();
     while tokens.peek().is_some() {
         if let Some(html_block) = parse_html_block(tokens)? {
@@ -289,3 +311,288 @@ pub fn parse(tokens: &mut Lexer<LoadedSource>) -> Result<ASTNode, (Option<usize>
     }
     Ok(ASTNode::Root(html_blocks))
 }
+
+/// Parses tokens into an Abstract Syntax Tree, additionally returning the byte-offset [Span] of
+/// each of [ASTNode::Root]'s children, in the same order. Used by the generator to emit source
+/// maps back to the original Jib source.
+///
+/// # Examples
+///
+/// ```
+/// use jib::parser::{parse_with_spans, ASTNode};
+/// use jib::lexer::{Lexer, Span};
+///
+/// let mut lexer = Lexer::new().load_source("<template>Hi!</template>".to_string());
+/// let (ast_root, spans) = parse_with_spans(&mut lexer).unwrap();
+/// assert_eq!(ast_root, ASTNode::Root(vec![ASTNode::Template("Hi!".to_string())]));
+/// assert_eq!(spans, vec![Span { start: 0, end: 24 }]);
+/// ```
+pub fn parse_with_spans(
+    tokens: &mut Lexer<LoadedSource>,
+) -> Result<(ASTNode, Vec<Span>), ParseError> {
+    let mut html_blocks = Vec::new();
+    let mut spans = Vec::new();
+
+    while tokens.peek().is_some() {
+        tokens.flush_whitespace();
+        // `tokens.offset()` would already point past the upcoming token here: `flush_whitespace`
+        // peeks to check each token's type, and `peek` runs the real scan (advancing the
+        // lexer's offset) as soon as it needs to classify what comes next, only caching the
+        // result rather than "un-scanning" it. The peeked token's own span start is the true
+        // start of this block.
+        let start = tokens
+            .peek()
+            .map(|token| token.span.start)
+            .unwrap_or_else(|| tokens.offset());
+        if let Some(html_block) = parse_html_block(tokens)? {
+            spans.push(Span {
+                start,
+                end: tokens.offset(),
+            });
+            html_blocks.push(html_block);
+        }
+    }
+
+    Ok((ASTNode::Root(html_blocks), spans))
+}
+
+/// A diagnostic produced while parsing, used by [parse_recovering] to report every syntax error
+/// found in a source file instead of bailing out on the first one.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The error that was encountered.
+    pub error: ParseError,
+}
+
+/// Returns `true` if `token_type` is a safe point to resume parsing after an error.
+fn is_sync_point(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::SemiColon
+            | TokenType::Pipe
+            | TokenType::CurlyBracketClose
+            | TokenType::TagScriptEnd
+            | TokenType::TagStyleEnd
+            | TokenType::TagTemplateEnd
+    )
+}
+
+/// Skips tokens until a safe synchronization point has been consumed.
+///
+/// Nested `<script>`/`<style>`/`<template>` blocks are tracked, so sync points inside a nested
+/// block are skipped along with it. The closing tag of the enclosing block is not consumed once
+/// at least one token has been skipped; it is left for that block's own parser. Always consumes
+/// at least one token, so the caller is guaranteed to make progress.
+fn synchronize(tokens: &mut Lexer<LoadedSource>) {
+    let mut open_blocks = 0;
+    let mut consumed_any = false;
+    loop {
+        let Some(token) = tokens.peek() else {
+            return;
+        };
+
+        match token.token_type {
+            TokenType::TagScriptStart | TokenType::TagStyleStart | TokenType::TagTemplateStart => {
+                open_blocks += 1;
+            }
+            TokenType::TagScriptEnd | TokenType::TagStyleEnd | TokenType::TagTemplateEnd
+                if open_blocks > 0 =>
+            {
+                open_blocks -= 1;
+            }
+            TokenType::TagScriptEnd | TokenType::TagStyleEnd | TokenType::TagTemplateEnd
+                if consumed_any =>
+            {
+                return;
+            }
+            _ if open_blocks == 0 && is_sync_point(&token.token_type) => {
+                tokens.next();
+                return;
+            }
+            _ => {}
+        }
+        tokens.next();
+        consumed_any = true;
+    }
+}
+
+/// Parses an enum declaration, recording a [Diagnostic] and synchronizing instead of bailing out
+/// if it cannot be parsed.
+fn parse_enum_recovering(
+    tokens: &mut Lexer<LoadedSource>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ASTNode> {
+    match parse_enum(tokens) {
+        Ok(node) => node,
+        Err(error) => {
+            diagnostics.push(Diagnostic { error });
+            synchronize(tokens);
+            None
+        }
+    }
+}
+
+/// Parses a single statement, recording a [Diagnostic] and synchronizing instead of bailing out
+/// if it cannot be parsed.
+fn parse_statement_recovering(
+    tokens: &mut Lexer<LoadedSource>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ASTNode> {
+    let token = tokens.peek()?;
+
+    match token.token_type {
+        TokenType::Comment => match parse_comment(tokens) {
+            Ok(node) => node,
+            Err(error) => {
+                diagnostics.push(Diagnostic { error });
+                synchronize(tokens);
+                None
+            }
+        },
+        TokenType::Keyword => match token.value.as_str() {
+            "enum" => parse_enum_recovering(tokens, diagnostics),
+            &_ => {
+                diagnostics.push(Diagnostic {
+                    error: ParseError::Syntax {
+                        message: format!("Unsupported keyword `{}`", token.value),
+                        line: Some(token.line_number),
+                        column: Some(token.column),
+                        span: Some(token.span),
+                    },
+                });
+                synchronize(tokens);
+                None
+            }
+        },
+        _ => {
+            tokens.next();
+            None
+        }
+    }
+}
+
+/// Parses a script block, recording a [Diagnostic] for every statement that fails to parse
+/// instead of bailing out on the first one.
+fn parse_script_block_recovering(
+    tokens: &mut Lexer<LoadedSource>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> ASTNode {
+    let mut statements = Vec::new();
+    loop {
+        let Some(token) = tokens.peek() else {
+            diagnostics.push(Diagnostic {
+                error: ParseError::UnclosedBlock { tag: "script" },
+            });
+            break;
+        };
+
+        match token.token_type {
+            TokenType::TagScriptEnd => {
+                tokens.next();
+                break;
+            }
+            TokenType::Whitespace | TokenType::Newline => {
+                tokens.next();
+            }
+            _ => {
+                if let Some(statement) = parse_statement_recovering(tokens, diagnostics) {
+                    statements.push(statement);
+                }
+            }
+        }
+    }
+    ASTNode::Script(statements)
+}
+
+/// Parses a single HTML block (`<template>`, `<style>` or `<script>`), recording a [Diagnostic]
+/// and synchronizing instead of bailing out if it cannot be parsed.
+fn parse_html_block_recovering(
+    tokens: &mut Lexer<LoadedSource>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<ASTNode> {
+    tokens.flush_whitespace();
+
+    let next_token = tokens.next()?;
+
+    match next_token.token_type {
+        TokenType::TagTemplateStart => match parse_template_block(tokens) {
+            Ok(node) => node,
+            Err(error) => {
+                diagnostics.push(Diagnostic { error });
+                synchronize(tokens);
+                None
+            }
+        },
+        TokenType::TagStyleStart => match parse_style_block(tokens) {
+            Ok(node) => node,
+            Err(error) => {
+                diagnostics.push(Diagnostic { error });
+                synchronize(tokens);
+                None
+            }
+        },
+        TokenType::TagScriptStart => Some(parse_script_block_recovering(tokens, diagnostics)),
+        _ => {
+            diagnostics.push(Diagnostic {
+                error: ParseError::UnexpectedToken {
+                    found: next_token.token_type,
+                    expected: vec![
+                        TokenType::TagTemplateStart,
+                        TokenType::TagStyleStart,
+                        TokenType::TagScriptStart,
+                    ],
+                    line: Some(next_token.line_number),
+                    column: Some(next_token.column),
+                    span: Some(next_token.span),
+                },
+            });
+            synchronize(tokens);
+            None
+        }
+    }
+}
+
+/// Parses tokens into an Abstract Syntax Tree, collecting every [Diagnostic] encountered
+/// instead of bailing out on the first syntax error.
+///
+/// # Examples
+///
+/// ```
+/// use jib::parser::{parse_recovering, ASTNode};
+/// use jib::lexer::Lexer;
+///
+/// let mut lexer = Lexer::new().load_source("<script>enum = {}</script>".to_string());
+/// let (ast_root, diagnostics) = parse_recovering(&mut lexer);
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(ast_root, ASTNode::Root(vec![ASTNode::Script(vec![])]));
+/// ```
+///
+/// Two independent mistakes in separate blocks are both reported, and parsing still resumes and
+/// completes after each one instead of stopping at the first:
+///
+/// ```
+/// use jib::parser::{parse_recovering, ASTNode};
+/// use jib::lexer::Lexer;
+///
+/// let mut lexer = Lexer::new().load_source(
+///     "<script>enum = {}</script><script>enum 1Bad = { X }</script>".to_string(),
+/// );
+/// let (ast_root, diagnostics) = parse_recovering(&mut lexer);
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(
+///     ast_root,
+///     ASTNode::Root(vec![ASTNode::Script(vec![]), ASTNode::Script(vec![])])
+/// );
+/// ```
+pub fn parse_recovering(tokens: &mut Lexer<LoadedSource>) -> (ASTNode, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let mut html_blocks = Vec::new();
+
+    while tokens.peek().is_some() {
+        if let Some(html_block) = parse_html_block_recovering(tokens, &mut diagnostics) {
+            html_blocks.push(html_block);
+        }
+    }
+
+    (ASTNode::Root(html_blocks), diagnostics)
+}